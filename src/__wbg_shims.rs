@@ -0,0 +1,57 @@
+// Copyright 2018 Developers of the Rand project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! JS bindings used by the wasm-bindgen backend, kept separate from
+//! `wasm32_bindgen.rs` so the `#[wasm_bindgen]` extern block doesn't clutter
+//! the actual RNG logic.
+
+pub use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    pub type Function;
+
+    #[wasm_bindgen(constructor)]
+    pub fn new(s: &str) -> Function;
+
+    #[wasm_bindgen(method)]
+    pub fn call(this: &Function, self_: &JsValue) -> JsValue;
+
+    #[derive(Clone, Debug)]
+    pub type This;
+
+    #[wasm_bindgen(method, getter, structural, js_name = self)]
+    pub fn self_(me: &This) -> JsValue;
+
+    #[wasm_bindgen(method, getter, structural)]
+    pub fn crypto(me: &This) -> JsValue;
+
+    #[derive(Clone, Debug)]
+    pub type NodeCrypto;
+
+    #[wasm_bindgen(method, js_name = randomFillSync, structural)]
+    pub fn random_fill_sync(this: &NodeCrypto, buf: &mut [u8]);
+
+    #[derive(Clone, Debug)]
+    pub type BrowserCrypto;
+
+    #[wasm_bindgen(method, getter, js_name = getRandomValues, structural)]
+    pub fn get_random_values_fn(this: &BrowserCrypto) -> JsValue;
+
+    #[wasm_bindgen(method, js_name = getRandomValues, structural)]
+    pub fn get_random_values(this: &BrowserCrypto, buf: &mut [u8]);
+
+    #[wasm_bindgen(js_name = require)]
+    pub fn node_require(s: &str) -> NodeCrypto;
+
+    // `globalThis.crypto`, read independent of the `This::self_()` browser
+    // check so it also resolves in Deno and Node 18+, where `globalThis` is
+    // defined but `self` is not.
+    #[wasm_bindgen(getter, js_namespace = globalThis, js_name = crypto)]
+    pub fn global_crypto() -> JsValue;
+}
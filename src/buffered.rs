@@ -0,0 +1,251 @@
+// Copyright 2018 Developers of the Rand project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A userspace-buffered CSPRNG that amortizes the cost of crossing the
+//! JS/WASM FFI boundary for small requests.
+//!
+//! Enabled via the `wasm_bindgen_buffered_rng` feature. The OS RNG source is
+//! used to seed a ChaCha20 keystream once per thread; subsequent small
+//! requests are served from that keystream instead of making a fresh FFI
+//! call (and, on the browser path, a fresh `getRandomValues` quota check)
+//! per request. The keystream is reseeded from the OS source after a
+//! configurable byte budget has been served (1 MiB by default, see
+//! [`set_reseed_budget`]), bounding how much output can ever be produced
+//! from a single 32-byte seed.
+//!
+//! There is no `fork()` on WASM, so unlike native userspace CSPRNGs this
+//! does not need to detect or reseed across a fork.
+
+use std::cell::{Cell, RefCell};
+
+use crate::Error;
+
+/// Default number of bytes served from a single seed before a reseed from
+/// the OS source, absent a call to [`set_reseed_budget`].
+const DEFAULT_RESEED_BUDGET: usize = 1024 * 1024;
+
+thread_local!(
+    static RESEED_BUDGET: Cell<usize> = Cell::new(DEFAULT_RESEED_BUDGET);
+);
+
+/// Override how many bytes are served from a single seed, on this thread,
+/// before a fresh reseed from the OS source. The reseed happens as soon as
+/// the budget is used up, in the middle of a `getrandom` call if need be.
+/// Security-sensitive callers that want the OS source consulted more often
+/// (down to before every byte, with `bytes = 0`) can use this to opt out of
+/// the default 1 MiB budget.
+pub fn set_reseed_budget(bytes: usize) {
+    RESEED_BUDGET.with(|b| b.set(bytes));
+}
+
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] ^= state[a];
+    state[d] = state[d].rotate_left(16);
+
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] ^= state[c];
+    state[b] = state[b].rotate_left(12);
+
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] ^= state[a];
+    state[d] = state[d].rotate_left(8);
+
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] ^= state[c];
+    state[b] = state[b].rotate_left(7);
+}
+
+// One ChaCha20 block (20 rounds) for `key` at the given 64-bit block index.
+// Uses the original (non-IETF) layout: a 64-bit block counter in words
+// 12-13 and a zero 64-bit nonce in words 14-15, since we only ever draw a
+// single stream from each seed.
+fn block(key: &[u32; 8], counter: u64) -> [u8; 64] {
+    let mut state = [0u32; 16];
+    state[0..4].copy_from_slice(&CONSTANTS);
+    state[4..12].copy_from_slice(key);
+    state[12] = counter as u32;
+    state[13] = (counter >> 32) as u32;
+
+    let mut working = state;
+    for _ in 0..10 {
+        quarter_round(&mut working, 0, 4, 8, 12);
+        quarter_round(&mut working, 1, 5, 9, 13);
+        quarter_round(&mut working, 2, 6, 10, 14);
+        quarter_round(&mut working, 3, 7, 11, 15);
+        quarter_round(&mut working, 0, 5, 10, 15);
+        quarter_round(&mut working, 1, 6, 11, 12);
+        quarter_round(&mut working, 2, 7, 8, 13);
+        quarter_round(&mut working, 3, 4, 9, 14);
+    }
+
+    let mut out = [0u8; 64];
+    for (i, word) in working.iter().enumerate() {
+        let word = word.wrapping_add(state[i]);
+        out[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    out
+}
+
+struct Drbg {
+    key: [u32; 8],
+    counter: u64,
+    keystream: [u8; 64],
+    // Number of bytes of `keystream` already consumed; 64 means empty.
+    keystream_pos: usize,
+    bytes_since_reseed: usize,
+}
+
+impl Drbg {
+    fn seeded(reseed: &mut impl FnMut(&mut [u8]) -> Result<(), Error>) -> Result<Drbg, Error> {
+        let mut seed = [0u8; 32];
+        reseed(&mut seed)?;
+
+        let mut key = [0u32; 8];
+        for (word, chunk) in key.iter_mut().zip(seed.chunks_exact(4)) {
+            *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+
+        Ok(Drbg {
+            key,
+            counter: 0,
+            keystream: [0u8; 64],
+            keystream_pos: 64,
+            bytes_since_reseed: 0,
+        })
+    }
+
+    fn needs_reseed(&self) -> bool {
+        self.bytes_since_reseed >= RESEED_BUDGET.with(Cell::get)
+    }
+
+    fn fill(&mut self, dest: &mut [u8]) {
+        let mut written = 0;
+        while written < dest.len() {
+            if self.keystream_pos == self.keystream.len() {
+                self.keystream = block(&self.key, self.counter);
+                self.counter = self.counter.wrapping_add(1);
+                self.keystream_pos = 0;
+            }
+            let available = self.keystream.len() - self.keystream_pos;
+            let n = available.min(dest.len() - written);
+            dest[written..written + n]
+                .copy_from_slice(&self.keystream[self.keystream_pos..self.keystream_pos + n]);
+            self.keystream_pos += n;
+            written += n;
+        }
+        self.bytes_since_reseed += dest.len();
+    }
+}
+
+thread_local!(
+    static DRBG: RefCell<Option<Drbg>> = RefCell::new(None);
+);
+
+/// Fill `dest` from the buffered keystream, reseeding from `reseed` (the
+/// real OS RNG source) on first use and whenever the reseed budget has been
+/// exhausted.
+pub fn fill(
+    dest: &mut [u8],
+    mut reseed: impl FnMut(&mut [u8]) -> Result<(), Error>,
+) -> Result<(), Error> {
+    DRBG.with(|cell| {
+        let mut slot = cell.borrow_mut();
+        let mut written = 0;
+        // Split `dest` on reseed-budget boundaries so a single oversized
+        // request can't drain far more than `RESEED_BUDGET` bytes from one
+        // seed; checking only once up front would miss that.
+        while written < dest.len() {
+            if slot.as_ref().map_or(true, Drbg::needs_reseed) {
+                *slot = Some(Drbg::seeded(&mut reseed)?);
+            }
+            let drbg = slot.as_mut().unwrap();
+            let budget = RESEED_BUDGET.with(Cell::get);
+            // `.max(1)` guarantees progress even with `budget == 0` (reseed
+            // before every byte), rather than looping forever on `n == 0`.
+            let n = budget
+                .saturating_sub(drbg.bytes_since_reseed)
+                .max(1)
+                .min(dest.len() - written);
+            drbg.fill(&mut dest[written..written + n]);
+            written += n;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7539 / 8439 §2.3.2 keystream block test vector: all-zero key,
+    // all-zero nonce, block counter 0. Our 64-bit-counter layout and the
+    // IETF 32-bit-counter/96-bit-nonce layout agree whenever every word
+    // past the key is zero, which is the case here.
+    #[test]
+    fn block_matches_rfc8439_test_vector() {
+        let key = [0u32; 8];
+        let expected: [u8; 64] = [
+            0x76, 0xb8, 0xe0, 0xad, 0xa0, 0xf1, 0x3d, 0x90, 0x40, 0x5d, 0x6a, 0xe5, 0x53, 0x86,
+            0xbd, 0x28, 0xbd, 0xd2, 0x19, 0xb8, 0xa0, 0x8d, 0xed, 0x1a, 0xa8, 0x36, 0xef, 0xcc,
+            0x8b, 0x77, 0x0d, 0xc7, 0xda, 0x41, 0x59, 0x7c, 0x51, 0x57, 0x48, 0x8d, 0x77, 0x24,
+            0xe0, 0x3f, 0xb8, 0xd8, 0x4a, 0x37, 0x6a, 0x43, 0xb8, 0xf4, 0x15, 0x18, 0xa1, 0x1c,
+            0xc3, 0x87, 0xb6, 0x69, 0xb2, 0xee, 0x65, 0x86,
+        ];
+        assert_eq!(block(&key, 0), expected);
+    }
+
+    #[test]
+    fn fill_consumes_keystream_before_requesting_a_new_block() {
+        let key = [0u32; 8];
+        let mut drbg = Drbg {
+            key,
+            counter: 0,
+            keystream: [0u8; 64],
+            keystream_pos: 64,
+            bytes_since_reseed: 0,
+        };
+
+        let mut first_half = [0u8; 32];
+        drbg.fill(&mut first_half);
+        assert_eq!(drbg.counter, 1, "one block should have been generated");
+        assert_eq!(&first_half[..], &block(&key, 0)[..32]);
+
+        let mut second_half = [0u8; 32];
+        drbg.fill(&mut second_half);
+        assert_eq!(drbg.counter, 1, "the rest should come from the same block");
+        assert_eq!(&second_half[..], &block(&key, 0)[32..]);
+    }
+
+    #[test]
+    fn reseeds_after_budget_is_exhausted() {
+        set_reseed_budget(64);
+        let mut reseed_calls = 0u8;
+        let mut dest = [0u8; 64];
+
+        fill(&mut dest, |buf| {
+            reseed_calls += 1;
+            buf.fill(reseed_calls);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(reseed_calls, 1);
+
+        // The budget was exactly exhausted by the first call, so the next
+        // call must reseed again rather than reusing the old keystream.
+        fill(&mut dest, |buf| {
+            reseed_calls += 1;
+            buf.fill(reseed_calls);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(reseed_calls, 2);
+    }
+}
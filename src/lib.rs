@@ -0,0 +1,79 @@
+// Copyright 2018 Developers of the Rand project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Interface to the operating system's random number generator.
+//!
+//! The `wasm_bindgen_buffered_rng` feature trades a small amount of
+//! forward secrecy (output is reseeded from the OS source only every
+//! megabyte or so, not every call) for far fewer JS/WASM FFI crossings; it
+//! is off by default, in which case every request passes straight through
+//! to the OS source.
+
+#[cfg(any(target_arch = "wasm32", target_arch = "wasm64"))]
+mod __wbg_shims;
+#[cfg(any(target_arch = "wasm32", target_arch = "wasm64"))]
+mod utils;
+#[cfg(any(target_arch = "wasm32", target_arch = "wasm64"))]
+mod wasm32_bindgen;
+
+// Gated on the feature alone (not the wasm target): the DRBG math has no
+// JS/WASM dependency, so this also builds and unit-tests on the host.
+#[cfg(feature = "wasm_bindgen_buffered_rng")]
+mod buffered;
+
+#[cfg(any(target_arch = "wasm32", target_arch = "wasm64"))]
+use wasm32_bindgen::getrandom_inner;
+#[cfg(any(target_arch = "wasm32", target_arch = "wasm64"))]
+pub use wasm32_bindgen::set_custom_source;
+#[cfg(feature = "wasm_bindgen_buffered_rng")]
+pub use buffered::set_reseed_budget;
+
+/// The error type for this crate's operations.
+///
+/// Unlike most `Error` types, this one is small and `Copy`: it records that
+/// the underlying source was unavailable, plus (on backends that can
+/// determine it) a static string describing *why*.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Error(Repr);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Repr {
+    Unavailable,
+    Custom(&'static str),
+}
+
+impl Error {
+    /// A human-readable description of why the OS RNG was unavailable,
+    /// when the backend was able to determine one.
+    ///
+    /// Returns `None` for errors that carry no extra context beyond
+    /// "unavailable".
+    pub fn msg(&self) -> Option<&'static str> {
+        match self.0 {
+            Repr::Unavailable => None,
+            Repr::Custom(msg) => Some(msg),
+        }
+    }
+
+    pub(crate) fn custom(msg: &'static str) -> Error {
+        Error(Repr::Custom(msg))
+    }
+}
+
+/// The OS RNG is unavailable, with no further detail available.
+pub const UNAVAILABLE_ERROR: Error = Error(Repr::Unavailable);
+
+/// Fill `dest` with random bytes from the system's preferred random number
+/// source.
+///
+/// Only implemented for `wasm32`/`wasm64` targets via wasm-bindgen; this
+/// checkout carries no other backend.
+#[cfg(any(target_arch = "wasm32", target_arch = "wasm64"))]
+pub fn getrandom(dest: &mut [u8]) -> Result<(), Error> {
+    getrandom_inner(dest)
+}
@@ -0,0 +1,31 @@
+// Copyright 2019 Developers of the Rand project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Shared utilities for implementing `getrandom` backends.
+
+use std::cell::RefCell;
+
+use crate::Error;
+
+/// Lazily initialize `once` via `init`, then invoke `f` with the result.
+///
+/// This is a small helper for the common "thread-local, initialize on
+/// first use" pattern used by backends that need to cache an expensive
+/// handle (e.g. a detected JS RNG source) for the lifetime of the thread.
+pub fn use_init<T, F, R>(once: &RefCell<Option<T>>, init: F, f: impl FnOnce(&T) -> R) -> Result<R, Error>
+where
+    F: FnOnce() -> Result<T, Error>,
+{
+    if let Some(ref val) = *once.borrow() {
+        return Ok(f(val));
+    }
+    let val = init()?;
+    let result = f(&val);
+    *once.borrow_mut() = Some(val);
+    Ok(result)
+}
@@ -9,14 +9,19 @@
 //! Implementation for WASM via wasm-bindgen
 
 use std::cell::RefCell;
-use std::mem;
 
 use wasm_bindgen::prelude::*;
 
 use super::__wbg_shims::*;
-use super::{Error, UNAVAILABLE_ERROR};
+use super::Error;
 use super::utils::use_init;
 
+// The wasm-bindgen slice ABI marshals `&mut [u8]` as a (ptr, len) pair
+// regardless of pointer width, so this backend works on both wasm32 and the
+// emerging wasm64-unknown-unknown target. Anything narrower or wider than
+// that isn't a wasm target `wasm-bindgen` supports.
+#[cfg(not(any(target_pointer_width = "32", target_pointer_width = "64")))]
+compile_error!("wasm32_bindgen backend requires a 32- or 64-bit wasm target");
 
 #[derive(Clone, Debug)]
 pub enum RngSource {
@@ -26,10 +31,50 @@ pub enum RngSource {
 
 thread_local!(
     static RNG_SOURCE: RefCell<Option<RngSource>> = RefCell::new(None);
+    static CUSTOM_SOURCE: RefCell<Option<Box<dyn Fn(&mut [u8]) -> Result<(), Error>>>> =
+        RefCell::new(None);
 );
 
+/// Register a custom RNG source, consulted before the built-in
+/// browser/Node.js autodetection.
+///
+/// This is an escape hatch for JS environments that `getrandom_init`'s
+/// detection logic doesn't recognize (React Native, Cloudflare Workers, an
+/// embedded JS runtime on an esp device, ...). Once
+/// registered, every call to `getrandom_inner` on this thread is routed
+/// to `source` instead of the autodetected `RngSource`. `source` is
+/// checked on every call, ahead of the (separately cached) autodetected
+/// source, so calling this takes effect immediately — even after
+/// `getrandom` has already run and autodetection has been cached.
+///
+/// With the `wasm_bindgen_buffered_rng` feature enabled, `source` is only
+/// consulted to reseed the buffered DRBG, not on every call — see the
+/// [`buffered`](crate::buffered) module docs.
+pub fn set_custom_source(source: impl Fn(&mut [u8]) -> Result<(), Error> + 'static) {
+    CUSTOM_SOURCE.with(|f| *f.borrow_mut() = Some(Box::new(source)));
+}
+
 pub fn getrandom_inner(dest: &mut [u8]) -> Result<(), Error> {
-    assert_eq!(mem::size_of::<usize>(), 4);
+    #[cfg(feature = "wasm_bindgen_buffered_rng")]
+    {
+        crate::buffered::fill(dest, fill_direct)
+    }
+    #[cfg(not(feature = "wasm_bindgen_buffered_rng"))]
+    {
+        fill_direct(dest)
+    }
+}
+
+// Fill `dest` straight from the custom source (if any) or the
+// autodetected `RngSource`, with no userspace buffering in between. This
+// is the whole of `getrandom_inner` unless the `wasm_bindgen_buffered_rng`
+// feature is enabled, in which case it also serves as the reseed source
+// for the buffered DRBG.
+fn fill_direct(dest: &mut [u8]) -> Result<(), Error> {
+    let custom = CUSTOM_SOURCE.with(|f| f.borrow().as_ref().map(|source| source(dest)));
+    if let Some(result) = custom {
+        return result;
+    }
 
     RNG_SOURCE.with(|f| {
         use_init(f, getrandom_init, |source| {
@@ -50,43 +95,52 @@ pub fn getrandom_inner(dest: &mut [u8]) -> Result<(), Error> {
             Ok(())
         })
     })
-
 }
 
 fn getrandom_init() -> Result<RngSource, Error> {
-    // First up we need to detect if we're running in node.js or a
-    // browser. To do this we get ahold of the `this` object (in a bit
-    // of a roundabout fashion).
+    // Prefer a WebCrypto-style `crypto.getRandomValues` reachable from the
+    // global object. This covers browsers, web workers, Deno, and Node 18+,
+    // where `globalThis.crypto` exists even in ES-module contexts or under
+    // bundlers where CommonJS `require` isn't available.
+    let crypto = global_crypto();
+    if !crypto.is_undefined() {
+        let crypto: BrowserCrypto = crypto.into();
+        if !crypto.get_random_values_fn().is_undefined() {
+            return Ok(RngSource::Browser(crypto));
+        }
+    }
+
+    // No global WebCrypto found. Fall back to classic (non-ESM) Node, where
+    // `require("crypto")` gives us `randomFillSync` instead.
     //
-    // Once we have `this` we look at its `self` property, which is
-    // only defined on the web (either a main window or web worker).
+    // To tell a browser without `crypto.getRandomValues` (OS RNG truly
+    // unavailable) apart from Node (where `require` is the right call), we
+    // get ahold of the `this` object and look at its `self` property, which
+    // is only defined on the web (either a main window or web worker).
     let this = Function::new("return this").call(&JsValue::undefined());
-    assert!(this != JsValue::undefined());
-    let this = This::from(this);
-    let is_browser = this.self_() != JsValue::undefined();
-
-    if !is_browser {
-        return Ok(RngSource::Node(node_require("crypto")))
+    if this == JsValue::undefined() {
+        let msg = "neither globalThis.crypto nor Node's `this` could be found";
+        return Err(Error::custom(msg));
     }
+    let this = This::from(this);
+    if this.self_() != JsValue::undefined() {
+        // We're in a browser context but didn't find a usable
+        // `globalThis.crypto` above; re-probe `self.crypto` directly so we
+        // can report exactly which step of the chain is missing.
+        let crypto = this.crypto();
+        if crypto.is_undefined() {
+            let msg = "self.crypto is undefined";
+            return Err(Error::custom(msg));
+        }
 
-    // If `self` is defined then we're in a browser somehow (main window
-    // or web worker). Here we want to try to use
-    // `crypto.getRandomValues`, but if `crypto` isn't defined we assume
-    // we're in an older web browser and the OS RNG isn't available.
-    let crypto = this.crypto();
-    if crypto.is_undefined() {
-        let msg = "self.crypto is undefined";
-        return Err(UNAVAILABLE_ERROR)   // TODO: report msg
-    }
+        let crypto: BrowserCrypto = crypto.into();
+        if crypto.get_random_values_fn().is_undefined() {
+            let msg = "crypto.getRandomValues is undefined";
+            return Err(Error::custom(msg));
+        }
 
-    // Test if `crypto.getRandomValues` is undefined as well
-    let crypto: BrowserCrypto = crypto.into();
-    if crypto.get_random_values_fn().is_undefined() {
-        let msg = "crypto.getRandomValues is undefined";
-        return Err(UNAVAILABLE_ERROR)   // TODO: report msg
+        return Ok(RngSource::Browser(crypto));
     }
 
-    // Ok! `self.crypto.getRandomValues` is a defined value, so let's
-    // assume we can do browser crypto.
-    Ok(RngSource::Browser(crypto))
+    Ok(RngSource::Node(node_require("crypto")))
 }